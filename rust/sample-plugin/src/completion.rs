@@ -0,0 +1,590 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Completion support for the sample plugin.
+//!
+//! Completions are built in two phases, following the shape used by
+//! rust-analyzer and similar tools: first we analyze the buffer at the
+//! cursor to build a `CompletionContext`, then we run an ordered list of
+//! independent providers, each of which inspects the context and pushes
+//! whatever `CompletionItem`s it can produce into a shared
+//! `CompletionAccumulator`. This keeps each completion source (buffer
+//! words, paths, keywords, snippets, ...) self-contained, and makes it
+//! easy to add another one without touching the others.
+
+use std::collections::HashSet;
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
+
+use xi_core::plugin_rpc::CompletionItem;
+use xi_rope::delta::Builder as EditBuilder;
+use xi_rope::interval::Interval;
+use xi_rope::rope::RopeDelta;
+
+/// The source that produced a `CompletionItem`, used to distinguish
+/// providers while merging their results.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// A word found elsewhere in the buffer.
+    Word,
+    /// A language keyword.
+    Keyword,
+    /// An entry found while completing a filesystem path.
+    Path,
+    /// A snippet expanding to a multi-stop skeleton.
+    Snippet,
+}
+
+impl CompletionKind {
+    /// A short human-readable label surfaced to the user as the
+    /// completion item's `detail`, so the user can tell which provider an
+    /// item came from. Items are deduplicated by label, so of a buffer word
+    /// and an identically-named keyword only the first one pushed is kept.
+    fn label(self) -> &'static str {
+        match self {
+            CompletionKind::Word => "buffer word",
+            CompletionKind::Keyword => "keyword",
+            CompletionKind::Path => "path",
+            CompletionKind::Snippet => "snippet",
+        }
+    }
+}
+
+/// The most results a single provider will contribute before the
+/// response is marked incomplete, so the front-end knows to re-query
+/// with a narrower prefix rather than assume it has everything.
+const MAX_RESULTS: usize = 50;
+
+/// The analyzed state of the buffer at the completion position, shared
+/// (read-only) across every provider.
+pub struct CompletionContext {
+    /// Byte offset of the cursor.
+    pub pos: usize,
+    /// Byte offset where `word` begins.
+    pub word_start: usize,
+    /// The partial word immediately preceding `pos`.
+    pub word: String,
+    /// The non-word character immediately preceding `word`, if any.
+    pub prev_char: Option<char>,
+    /// The contents of the current line, from its start up to `pos`.
+    pub line_prefix: String,
+    /// Path of the document on disk, if it has been saved.
+    pub doc_path: Option<PathBuf>,
+    /// Full text of the document, for providers that scan buffer contents.
+    pub doc_text: String,
+    /// Size of the buffer, needed to build `RopeDelta`s.
+    pub buf_size: usize,
+}
+
+impl CompletionContext {
+    /// Whether `pos` sits at the start of the line, ignoring leading
+    /// whitespace.
+    pub fn at_line_start(&self) -> bool {
+        self.line_prefix.trim_start().is_empty()
+    }
+
+    /// Whether `pos` is inside a line that starts with `//` (ignoring
+    /// leading whitespace); a `//` comment after code on the same line is
+    /// not detected.
+    pub fn in_line_comment(&self) -> bool {
+        self.line_prefix.trim_start().starts_with("//")
+    }
+
+    /// Builds the `RopeDelta` that replaces the partial word with `text`.
+    pub fn edit_for(&self, text: &str) -> RopeDelta {
+        self.edit_replacing_suffix(self.pos - self.word_start, text)
+    }
+
+    /// Builds the `RopeDelta` that replaces the last `suffix_len` bytes
+    /// before `pos` with `text`, e.g. just the final segment of a path.
+    pub fn edit_replacing_suffix(&self, suffix_len: usize, text: &str) -> RopeDelta {
+        let iv = Interval::new(self.pos - suffix_len, self.pos);
+        let mut builder = EditBuilder::new(self.buf_size);
+        builder.replace(iv, text.into());
+        builder.build()
+    }
+}
+
+/// Accumulates `CompletionItem`s across providers, deduplicating by label
+/// and tracking whether any provider had to truncate its results.
+pub struct CompletionAccumulator {
+    items: Vec<(CompletionItem, i32)>,
+    seen_labels: HashSet<String>,
+    is_incomplete: bool,
+}
+
+impl CompletionAccumulator {
+    pub fn new() -> Self {
+        CompletionAccumulator { items: Vec::new(), seen_labels: HashSet::new(), is_incomplete: false }
+    }
+
+    /// Adds `item` unless a provider has already contributed that label.
+    /// `kind` is stamped onto the item's `detail` so the user can tell
+    /// providers apart.
+    pub fn push(&mut self, mut item: CompletionItem, kind: CompletionKind, score: i32) {
+        if self.seen_labels.insert(item.label.clone()) {
+            item.detail = Some(kind.label().to_owned());
+            self.items.push((item, score));
+        }
+    }
+
+    /// Marks the overall response as incomplete, e.g. because a provider
+    /// truncated its result set.
+    pub fn mark_incomplete(&mut self) {
+        self.is_incomplete = true;
+    }
+
+    /// Sorts the accumulated items by descending score (alphabetical on
+    /// ties) and returns them along with the overall `is_incomplete` flag.
+    pub fn finish(mut self) -> (Vec<CompletionItem>, bool) {
+        self.items.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.label.cmp(&b.0.label)));
+        let items = self.items.into_iter().map(|(item, _)| item).collect();
+        (items, self.is_incomplete)
+    }
+}
+
+/// A small set of keywords, to demonstrate a provider that isn't backed
+/// by the buffer or filesystem at all.
+const KEYWORDS: &[&str] =
+    &["fn", "let", "mut", "match", "struct", "enum", "impl", "return", "if", "else", "for", "while"];
+
+/// Base score awarded for each matched character.
+const SCORE_MATCH: i32 = 16;
+/// Bonus for a match right after a separator or at a camelCase boundary.
+const SCORE_WORD_BOUNDARY: i32 = 8;
+/// Bonus for a match that immediately follows the previous match.
+const SCORE_CONSECUTIVE: i32 = 4;
+/// Penalty per skipped candidate character between two matches.
+const PENALTY_GAP: i32 = 2;
+/// Penalty per candidate character skipped before the first match.
+const PENALTY_LEADING_GAP: i32 = 1;
+
+/// Scores `candidate` against `query` as a fuzzy, in-order subsequence
+/// match, or returns `None` if `query`'s characters don't all appear in
+/// order. Matching is case-insensitive; `candidate`'s original casing is
+/// unaffected.
+fn fuzzy_score(query: &str, candidate: &str) -> Option<i32> {
+    if query.is_empty() {
+        return None;
+    }
+    let query: Vec<char> = query.to_lowercase().chars().collect();
+    let chars: Vec<char> = candidate.chars().collect();
+
+    let mut score = 0;
+    let mut qi = 0;
+    let mut last_match: Option<usize> = None;
+    for (ci, &c) in chars.iter().enumerate() {
+        if qi >= query.len() {
+            break;
+        }
+        // Compare per-character rather than indexing a separately-built
+        // `candidate.to_lowercase()` vector: some characters lowercase to
+        // more than one char (e.g. 'İ'), which would desync that vector's
+        // indices from `chars` and panic on out-of-bounds access below.
+        if !c.to_lowercase().eq(std::iter::once(query[qi])) {
+            continue;
+        }
+
+        score += SCORE_MATCH;
+
+        let at_boundary = ci == 0
+            || matches!(chars[ci - 1], '_' | '-' | '/')
+            || chars[ci - 1].is_whitespace()
+            || (chars[ci - 1].is_lowercase() && chars[ci].is_uppercase());
+        if at_boundary {
+            score += SCORE_WORD_BOUNDARY;
+        }
+
+        match last_match {
+            Some(prev) if prev + 1 == ci => score += SCORE_CONSECUTIVE,
+            Some(prev) => score -= PENALTY_GAP * (ci - prev - 1) as i32,
+            None => score -= PENALTY_LEADING_GAP * ci as i32,
+        }
+
+        last_match = Some(ci);
+        qi += 1;
+    }
+
+    if qi == query.len() {
+        Some(score)
+    } else {
+        None
+    }
+}
+
+/// Completes `ctx.word` against the words already present in the
+/// document, ranking matches with a fuzzy subsequence scorer so that
+/// e.g. `cw` can find `CompletionWord`.
+pub fn complete_word_from_buffer(ctx: &CompletionContext, acc: &mut CompletionAccumulator) {
+    if ctx.word.is_empty() {
+        return;
+    }
+    let mut words: Vec<String> = ctx
+        .doc_text
+        .split(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .filter(|w| w.len() > ctx.word.len())
+        .map(|s| s.to_owned())
+        .collect();
+    words.sort_unstable();
+    words.dedup();
+
+    let mut scored: Vec<(String, i32)> =
+        words.into_iter().filter_map(|w| fuzzy_score(&ctx.word, &w).map(|score| (w, score))).collect();
+    scored.sort_by(|a, b| b.1.cmp(&a.1));
+    if scored.len() > MAX_RESULTS {
+        scored.truncate(MAX_RESULTS);
+        acc.mark_incomplete();
+    }
+
+    for (word, score) in scored {
+        let mut completion = CompletionItem::with_label(&word);
+        completion.edit = Some(ctx.edit_for(&word));
+        acc.push(completion, CompletionKind::Word, score);
+    }
+}
+
+/// Whether `word` looks enough like a filesystem path to be worth
+/// resolving, e.g. `src/comp`, `./foo`, `../foo` or `~/foo`.
+fn looks_like_path(word: &str) -> bool {
+    word.contains('/') || word.starts_with('~')
+}
+
+/// Splits a path-like word into its directory portion (including the
+/// trailing slash, if any) and its final, possibly partial, component.
+/// E.g. `"src/comp"` -> `("src/", "comp")`, `"foo"` -> `("", "foo")`,
+/// and a bare `"~"` or `"~foo"` (no `/` yet) -> `("~", "")` / `("~", "foo")`,
+/// so home-directory expansion doesn't require the user to type a slash
+/// before we have anything to resolve.
+fn split_path(word: &str) -> (&str, &str) {
+    match word.rfind('/') {
+        Some(idx) => (&word[..idx + 1], &word[idx + 1..]),
+        None if word.starts_with('~') => ("~", &word[1..]),
+        None => ("", word),
+    }
+}
+
+/// Resolves a path's directory portion to an absolute directory,
+/// expanding a leading `~` against the user's home directory and
+/// resolving other relative paths against the saved document's own
+/// directory.
+fn resolve_dir(ctx: &CompletionContext, dir_part: &str) -> Option<PathBuf> {
+    if let Some(rest) = dir_part.strip_prefix('~') {
+        return Some(dirs::home_dir()?.join(rest.trim_start_matches('/')));
+    }
+    if Path::new(dir_part).is_absolute() {
+        return Some(PathBuf::from(dir_part));
+    }
+    let base = ctx.doc_path.as_ref().and_then(|p| p.parent())?;
+    Some(base.join(dir_part))
+}
+
+/// Scores a directory entry against the partial final path component,
+/// appending a trailing `/` to directories so they can be descended into
+/// with another completion request.
+fn match_path_entry(entry: &DirEntry, partial: &str) -> Option<(String, i32)> {
+    let mut name = entry.file_name().to_string_lossy().into_owned();
+    let score = if partial.is_empty() { 0 } else { fuzzy_score(partial, &name)? };
+    if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+        name.push('/');
+    }
+    Some((name, score))
+}
+
+/// Completes `ctx.word` as a filesystem path: reads the directory named
+/// by its prefix and suggests entries matching the final component,
+/// replacing only that final component when accepted.
+pub fn complete_path(ctx: &CompletionContext, acc: &mut CompletionAccumulator) {
+    if !looks_like_path(&ctx.word) {
+        return;
+    }
+    let (dir_part, partial) = split_path(&ctx.word);
+    let dir = match resolve_dir(ctx, dir_part) {
+        Some(dir) => dir,
+        None => return,
+    };
+
+    let entries = match std::fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            info!("complete_path: could not read {}: {}", dir.display(), e);
+            return;
+        }
+    };
+
+    let mut matches: Vec<(String, i32)> =
+        entries.filter_map(Result::ok).filter_map(|entry| match_path_entry(&entry, partial)).collect();
+    matches.sort_by(|a, b| b.1.cmp(&a.1));
+    if matches.len() > MAX_RESULTS {
+        matches.truncate(MAX_RESULTS);
+        acc.mark_incomplete();
+    }
+
+    for (label, score) in matches {
+        let mut completion = CompletionItem::with_label(&label);
+        completion.edit = Some(ctx.edit_replacing_suffix(partial.len(), &label));
+        acc.push(completion, CompletionKind::Path, score);
+    }
+}
+
+/// Completes `ctx.word` against a small fixed list of keywords.
+pub fn complete_keyword(ctx: &CompletionContext, acc: &mut CompletionAccumulator) {
+    if ctx.word.is_empty() {
+        return;
+    }
+    for &keyword in KEYWORDS {
+        if keyword.starts_with(ctx.word.as_str()) && keyword.len() > ctx.word.len() {
+            let mut completion = CompletionItem::with_label(keyword);
+            completion.edit = Some(ctx.edit_for(keyword));
+            acc.push(completion, CompletionKind::Keyword, 0);
+        }
+    }
+}
+
+/// A built-in snippet, written with `$1`, `$2`, ... tab stops and
+/// `${1:placeholder}` placeholders, and an implicit or explicit final
+/// `$0` stop marking where the cursor lands after the last edit.
+struct Snippet {
+    label: &'static str,
+    body: &'static str,
+}
+
+const SNIPPETS: &[Snippet] = &[
+    Snippet { label: "for", body: "for ${1:item} in ${2:iter} {\n    $0\n}" },
+    Snippet { label: "match", body: "match ${1:expr} {\n    ${2:pat} => $0,\n}" },
+];
+
+/// A snippet body parsed into the literal text to insert, with
+/// placeholder defaults filled in, and the byte offsets of its tab
+/// stops (relative to the start of that text) in visit order. `$0`,
+/// the final stop, always comes last regardless of its position in
+/// the body.
+struct ParsedSnippet {
+    text: String,
+    tab_stops: Vec<usize>,
+}
+
+/// Parses `body`'s `$N` and `${N:placeholder}` tab stops into a
+/// `ParsedSnippet`.
+fn parse_snippet(body: &str) -> ParsedSnippet {
+    let chars: Vec<char> = body.chars().collect();
+    let mut text = String::new();
+    let mut stops: Vec<(u32, usize)> = Vec::new();
+    let mut final_stop = None;
+
+    let mut i = 0;
+    while i < chars.len() {
+        if chars[i] != '$' || i + 1 >= chars.len() {
+            text.push(chars[i]);
+            i += 1;
+            continue;
+        }
+
+        if chars[i + 1].is_ascii_digit() {
+            let start = i + 1;
+            let end = chars[start..].iter().take_while(|c| c.is_ascii_digit()).count() + start;
+            let n: u32 = chars[start..end].iter().collect::<String>().parse().unwrap();
+            record_stop(n, text.len(), &mut stops, &mut final_stop);
+            i = end;
+        } else if chars[i + 1] == '{' {
+            match chars[i..].iter().position(|&c| c == '}') {
+                Some(len) => {
+                    let inner: String = chars[i + 2..i + len].iter().collect();
+                    let mut parts = inner.splitn(2, ':');
+                    let n: u32 = parts.next().unwrap_or("0").parse().unwrap_or(0);
+                    let placeholder = parts.next().unwrap_or("");
+                    record_stop(n, text.len(), &mut stops, &mut final_stop);
+                    text.push_str(placeholder);
+                    i += len + 1;
+                }
+                None => {
+                    text.push(chars[i]);
+                    i += 1;
+                }
+            }
+        } else {
+            text.push(chars[i]);
+            i += 1;
+        }
+    }
+
+    stops.sort_by_key(|&(n, _)| n);
+    let mut tab_stops: Vec<usize> = stops.into_iter().map(|(_, offset)| offset).collect();
+    tab_stops.push(final_stop.unwrap_or_else(|| text.len()));
+    ParsedSnippet { text, tab_stops }
+}
+
+fn record_stop(n: u32, offset: usize, stops: &mut Vec<(u32, usize)>, final_stop: &mut Option<usize>) {
+    if n == 0 {
+        *final_stop = Some(offset);
+    } else {
+        stops.push((n, offset));
+    }
+}
+
+/// Completes `ctx.word` against a small set of built-in snippets, e.g. a
+/// `for` item that expands into a loop skeleton with cursor stops.
+///
+/// The item is marked `is_snippet` and carries `tab_stops` (offsets
+/// relative to the start of the inserted text) so the front-end can jump
+/// the cursor between them instead of inserting the placeholder defaults
+/// literally and leaving the cursor at the end.
+///
+/// NOTE: `is_snippet` and `tab_stops` are new fields on
+/// `xi_core_lib::plugin_rpc::CompletionItem` this commit depends on; the
+/// corresponding `xi_core_lib` change needs to land alongside this one
+/// before it will compile against that crate.
+pub fn complete_snippet(ctx: &CompletionContext, acc: &mut CompletionAccumulator) {
+    if ctx.word.is_empty() {
+        return;
+    }
+    for snippet in SNIPPETS {
+        if let Some(score) = fuzzy_score(&ctx.word, snippet.label) {
+            let parsed = parse_snippet(snippet.body);
+            let mut completion = CompletionItem::with_label(snippet.label);
+            completion.edit = Some(ctx.edit_for(&parsed.text));
+            completion.is_snippet = true;
+            completion.tab_stops = parsed.tab_stops;
+            acc.push(completion, CompletionKind::Snippet, score);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        complete_word_from_buffer, fuzzy_score, parse_snippet, resolve_dir, split_path,
+        CompletionAccumulator, CompletionContext,
+    };
+    use std::path::PathBuf;
+
+    fn ctx_with_doc_path(doc_path: Option<PathBuf>) -> CompletionContext {
+        CompletionContext {
+            pos: 0,
+            word_start: 0,
+            word: String::new(),
+            prev_char: None,
+            line_prefix: String::new(),
+            doc_path,
+            doc_text: String::new(),
+            buf_size: 0,
+        }
+    }
+
+    #[test]
+    fn complete_word_from_buffer_matches_snake_case_candidates() {
+        let mut ctx = ctx_with_doc_path(None);
+        ctx.word = "fb".to_owned();
+        ctx.doc_text = "let foo_bar = 1;".to_owned();
+
+        let mut acc = CompletionAccumulator::new();
+        complete_word_from_buffer(&ctx, &mut acc);
+        let (items, _) = acc.finish();
+        assert!(items.iter().any(|item| item.label == "foo_bar"));
+    }
+
+    #[test]
+    fn split_path_handles_bare_tilde() {
+        assert_eq!(split_path("~"), ("~", ""));
+    }
+
+    #[test]
+    fn split_path_handles_tilde_with_partial() {
+        assert_eq!(split_path("~foo"), ("~", "foo"));
+    }
+
+    #[test]
+    fn split_path_handles_tilde_with_slash() {
+        assert_eq!(split_path("~/foo"), ("~/", "foo"));
+    }
+
+    #[test]
+    fn split_path_handles_plain_word() {
+        assert_eq!(split_path("foo"), ("", "foo"));
+    }
+
+    #[test]
+    fn resolve_dir_expands_bare_tilde_to_home() {
+        let ctx = ctx_with_doc_path(None);
+        assert_eq!(resolve_dir(&ctx, "~"), dirs::home_dir());
+    }
+
+    #[test]
+    fn resolve_dir_resolves_relative_against_doc_dir() {
+        let ctx = ctx_with_doc_path(Some(PathBuf::from("/project/src/main.rs")));
+        assert_eq!(resolve_dir(&ctx, "sub/"), Some(PathBuf::from("/project/src/sub/")));
+    }
+
+    #[test]
+    fn fuzzy_score_requires_in_order_subsequence() {
+        assert!(fuzzy_score("cw", "CompletionWord").is_some());
+        assert!(fuzzy_score("wc", "CompletionWord").is_none());
+    }
+
+    #[test]
+    fn fuzzy_score_is_case_insensitive() {
+        assert!(fuzzy_score("COMP", "completion").is_some());
+    }
+
+    #[test]
+    fn fuzzy_score_empty_query_never_matches() {
+        assert_eq!(fuzzy_score("", "anything"), None);
+    }
+
+    #[test]
+    fn fuzzy_score_rewards_word_boundaries_over_scattered_matches() {
+        // "cw" hits two word-boundary letters in "CompletionWord"...
+        let boundary_score = fuzzy_score("cw", "CompletionWord").unwrap();
+        // ...but only one (and with a gap) in "completionword".
+        let scattered_score = fuzzy_score("cw", "completionword").unwrap();
+        assert!(boundary_score > scattered_score);
+    }
+
+    #[test]
+    fn fuzzy_score_rewards_consecutive_matches() {
+        let consecutive = fuzzy_score("ab", "abc").unwrap();
+        let gapped = fuzzy_score("ab", "axbc").unwrap();
+        assert!(consecutive > gapped);
+    }
+
+    #[test]
+    fn fuzzy_score_does_not_panic_on_chars_that_lowercase_to_multiple_chars() {
+        // 'İ' (U+0130) lowercases to two chars ("i" + a combining dot),
+        // so a candidate containing it has fewer `chars` than
+        // `to_lowercase().chars()` — this must not desync the indexing.
+        assert!(fuzzy_score("bul", "İstanbul").is_some());
+    }
+
+    #[test]
+    fn parse_snippet_fills_in_placeholder_defaults() {
+        let parsed = parse_snippet("for ${1:item} in ${2:iter} {\n    $0\n}");
+        assert_eq!(parsed.text, "for item in iter {\n    \n}");
+    }
+
+    #[test]
+    fn parse_snippet_orders_tab_stops_with_final_last() {
+        let parsed = parse_snippet("for ${1:item} in ${2:iter} {\n    $0\n}");
+        // stop 1 ("item") comes before stop 2 ("iter"), and the final
+        // stop ($0) is last regardless of where it appears in the body.
+        let stop1 = parsed.text.find("item").unwrap();
+        let stop2 = parsed.text.find("iter").unwrap();
+        assert_eq!(parsed.tab_stops, vec![stop1, stop2, parsed.text.len() - 2]);
+    }
+
+    #[test]
+    fn parse_snippet_handles_bare_numeric_stops_with_no_placeholder() {
+        let parsed = parse_snippet("$1, $2$0");
+        assert_eq!(parsed.text, ", ");
+        assert_eq!(parsed.tab_stops, vec![0, 2, 2]);
+    }
+}
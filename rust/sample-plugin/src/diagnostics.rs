@@ -0,0 +1,186 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A simple diagnostics provider for the sample plugin: analyzes the
+//! whole document and reports trailing whitespace, overly long lines,
+//! and TODO/FIXME markers as ranged annotations.
+
+use std::time::{Duration, Instant};
+
+use xi_core::ConfigTable;
+
+/// Minimum time between two diagnostics runs, so rapid keystrokes don't
+/// trigger a full-document scan each time. A run happens at once when this
+/// much time has passed since the previous run; an edit that arrives sooner
+/// is not analyzed, and its diagnostics wait for a later update or save.
+const DEBOUNCE: Duration = Duration::from_millis(250);
+
+/// Default line width above which a line is flagged, used until
+/// `config_changed` supplies one.
+const DEFAULT_MAX_WIDTH: usize = 100;
+
+/// The config key `config_changed` reads the line width limit from.
+const MAX_WIDTH_CONFIG_KEY: &str = "line_width";
+
+/// The annotation type diagnostics are pushed to core under.
+pub const ANNOTATION_TYPE: &str = "xi.diagnostics";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+/// A single diagnostic: a byte interval in the document, a severity,
+/// and a human-readable message.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub start: usize,
+    pub end: usize,
+    pub severity: Severity,
+    pub message: String,
+}
+
+/// Debounced diagnostics state for a single view.
+pub struct DiagnosticsState {
+    max_width: usize,
+    last_run: Option<Instant>,
+}
+
+impl DiagnosticsState {
+    pub fn new() -> Self {
+        DiagnosticsState { max_width: DEFAULT_MAX_WIDTH, last_run: None }
+    }
+
+    /// Reads `max_width` out of a config update, if present.
+    pub fn config_changed(&mut self, changes: &ConfigTable) {
+        if let Some(width) = changes.get(MAX_WIDTH_CONFIG_KEY).and_then(|v| v.as_i64()) {
+            self.max_width = width.max(0) as usize;
+        }
+    }
+
+    /// Re-analyzes `doc` and returns the fresh diagnostics, unless the
+    /// last run was too recent, in which case `None` is returned and the
+    /// caller should keep showing the previous results.
+    pub fn maybe_run(&mut self, doc: &str) -> Option<Vec<Diagnostic>> {
+        let now = Instant::now();
+        if let Some(last_run) = self.last_run {
+            if now.duration_since(last_run) < DEBOUNCE {
+                return None;
+            }
+        }
+        self.last_run = Some(now);
+        Some(collect_diagnostics(doc, self.max_width))
+    }
+}
+
+/// Scans `doc` for trailing whitespace, lines longer than `max_width`
+/// characters (no limit if `max_width` is `0`), and TODO/FIXME markers.
+pub fn collect_diagnostics(doc: &str, max_width: usize) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+    let mut offset = 0;
+
+    for raw_line in doc.split('\n') {
+        // Strip a trailing '\r' so CRLF documents don't have every line
+        // falsely flagged as having trailing whitespace; `offset` still
+        // advances by `raw_line.len()` below so byte offsets stay correct.
+        let line = raw_line.strip_suffix('\r').unwrap_or(raw_line);
+
+        let trimmed_len = line.trim_end().len();
+        if trimmed_len < line.len() {
+            diagnostics.push(Diagnostic {
+                start: offset + trimmed_len,
+                end: offset + line.len(),
+                severity: Severity::Warning,
+                message: "trailing whitespace".to_owned(),
+            });
+        }
+
+        if max_width > 0 && line.chars().count() > max_width {
+            diagnostics.push(Diagnostic {
+                start: offset,
+                end: offset + line.len(),
+                severity: Severity::Info,
+                message: format!("line exceeds {} characters", max_width),
+            });
+        }
+
+        for marker in &["TODO", "FIXME"] {
+            if let Some(idx) = line.find(marker) {
+                diagnostics.push(Diagnostic {
+                    start: offset + idx,
+                    end: offset + idx + marker.len(),
+                    severity: Severity::Info,
+                    message: format!("{} marker", marker),
+                });
+            }
+        }
+
+        offset += raw_line.len() + 1; // +1 for the '\n' split out by `split`
+    }
+
+    diagnostics
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{collect_diagnostics, Severity};
+
+    #[test]
+    fn collect_diagnostics_flags_trailing_whitespace() {
+        let doc = "let x = 1;  \nlet y = 2;";
+        let diagnostics = collect_diagnostics(doc, 0);
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Warning && d.message == "trailing whitespace"));
+    }
+
+    #[test]
+    fn collect_diagnostics_does_not_flag_crlf_line_endings() {
+        let doc = "let x = 1;\r\nlet y = 2;\r\n";
+        let diagnostics = collect_diagnostics(doc, 0);
+        assert!(!diagnostics.iter().any(|d| d.message == "trailing whitespace"));
+    }
+
+    #[test]
+    fn collect_diagnostics_flags_long_lines() {
+        let doc = "x".repeat(10);
+        let diagnostics = collect_diagnostics(&doc, 5);
+        assert!(diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Info && d.message == "line exceeds 5 characters"));
+    }
+
+    #[test]
+    fn collect_diagnostics_respects_zero_max_width_as_no_limit() {
+        let doc = "x".repeat(200);
+        let diagnostics = collect_diagnostics(&doc, 0);
+        assert!(diagnostics.is_empty());
+    }
+
+    #[test]
+    fn collect_diagnostics_flags_todo_and_fixme_markers() {
+        let doc = "// TODO: clean this up\n// FIXME: this is broken";
+        let diagnostics = collect_diagnostics(doc, 0);
+        assert!(diagnostics.iter().any(|d| d.message == "TODO marker"));
+        assert!(diagnostics.iter().any(|d| d.message == "FIXME marker"));
+    }
+
+    #[test]
+    fn collect_diagnostics_is_empty_for_clean_short_lines() {
+        let doc = "let x = 1;\nlet y = 2;";
+        assert!(collect_diagnostics(doc, 100).is_empty());
+    }
+}
@@ -23,10 +23,13 @@ extern crate xi_plugin_lib;
 extern crate xi_rope;
 extern crate xi_rpc;
 
-use std::fs::DirEntry;
+mod completion;
+mod diagnostics;
+mod rename;
+
 use std::path::{Path, PathBuf};
 
-use xi_core::plugin_rpc::{CompletionItem, CompletionResponse};
+use xi_core::plugin_rpc::CompletionResponse;
 use xi_core::ConfigTable;
 use xi_plugin_lib::{mainloop, ChunkCache, Error, Plugin, View};
 use xi_rope::delta::Builder as EditBuilder;
@@ -34,13 +37,21 @@ use xi_rope::interval::Interval;
 use xi_rope::rope::RopeDelta;
 use xi_rpc::RemoteError;
 
+use completion::{
+    complete_keyword, complete_path, complete_snippet, complete_word_from_buffer,
+    CompletionAccumulator, CompletionContext,
+};
+use diagnostics::{DiagnosticsState, ANNOTATION_TYPE};
+
 /// A type that implements the `Plugin` trait, and interacts with xi-core.
 ///
 /// Currently, this plugin has a single noteworthy behaviour,
 /// intended to demonstrate how to edit a document; when the plugin is active,
 /// and the user inserts an exclamation mark, the plugin will capitalize the
 /// preceding word.
-struct SamplePlugin;
+struct SamplePlugin {
+    diagnostics: DiagnosticsState,
+}
 
 //NOTE: implementing the `Plugin` trait is the sole requirement of a plugin.
 // For more documentation, see `rust/plugin-lib` in this repo.
@@ -57,9 +68,12 @@ impl Plugin for SamplePlugin {
 
     fn did_save(&mut self, view: &mut View<Self::Cache>, _old: Option<&Path>) {
         eprintln!("saved view {}", view.get_id());
+        self.run_diagnostics(view);
     }
 
-    fn config_changed(&mut self, _view: &mut View<Self::Cache>, _changes: &ConfigTable) {}
+    fn config_changed(&mut self, _view: &mut View<Self::Cache>, changes: &ConfigTable) {
+        self.diagnostics.config_changed(changes);
+    }
 
     fn update(
         &mut self,
@@ -77,18 +91,40 @@ impl Plugin for SamplePlugin {
                 let _ = self.capitalize_word(view, iv.end());
             }
         }
+        self.run_diagnostics(view);
     }
 
-    /// Handles a request for autocomplete, by attempting to complete file paths.
+    /// Handles a request to rename the identifier under `pos` to
+    /// `new_name`, analogous to `completions`: finds every occurrence of
+    /// that identifier across the document and rewrites them all as a
+    /// single atomic multi-region edit.
     ///
-    /// If the word under the cursor resembles a file path, this fn will attempt to
-    /// locate that path and find subitems, which it will return as completion suggestions.
+    /// NOTE: `rename` is new `Plugin` trait surface this commit depends
+    /// on (mirroring the existing `completions` entry point's signature
+    /// shape); the corresponding `xi_plugin_lib`/`xi_core_lib` change
+    /// needs to land alongside this one before it will compile against
+    /// those crates.
+    fn rename(&mut self, view: &mut View<Self::Cache>, pos: usize, new_name: String) {
+        if let Err(e) = self.rename_at(view, pos, &new_name) {
+            info!("error: {:?}", e);
+        }
+    }
+
+    /// Handles a request for autocomplete.
+    ///
+    /// Builds a `CompletionContext` describing the buffer at `pos`, then runs
+    /// every completion provider against it and merges their results. See
+    /// the `completion` module for the providers themselves.
     fn completions(&mut self, view: &mut View<Self::Cache>, request_id: usize, pos: usize) {
         info!("completions called : pos={}", pos);
-        let response = self.word_completions(view, pos).map(|items| CompletionResponse {
-            is_incomplete: false,
-            can_resolve: false,
-            items,
+        let response = self.build_completion_context(view, pos).map(|ctx| {
+            let mut acc = CompletionAccumulator::new();
+            complete_word_from_buffer(&ctx, &mut acc);
+            complete_path(&ctx, &mut acc);
+            complete_keyword(&ctx, &mut acc);
+            complete_snippet(&ctx, &mut acc);
+            let (items, is_incomplete) = acc.finish();
+            CompletionResponse { is_incomplete, can_resolve: false, items }
         });
 
         view.completions(request_id, response)
@@ -96,6 +132,10 @@ impl Plugin for SamplePlugin {
 }
 
 impl SamplePlugin {
+    fn new() -> Self {
+        SamplePlugin { diagnostics: DiagnosticsState::new() }
+    }
+
     /// Uppercases the word preceding `end_offset`.
     fn capitalize_word(&self, view: &mut View<ChunkCache>, end_offset: usize) -> Result<(), Error> {
         //NOTE: this makes it clear to me that we need a better API for edits
@@ -125,64 +165,87 @@ impl SamplePlugin {
         Ok(())
     }
 
-    fn complete_word(word: &str, text: &str) -> Vec<String> {
-        if word.len() == 0 {
-            vec![]
-        } else {
-            let mut words = text
-                .split(|c| !char::is_alphanumeric(c))
-                .filter(|w| w.starts_with(&word) && w.len() > word.len())
-                .map(|s| s.to_owned())
-                .collect::<Vec<String>>();
-            words.sort_unstable();
-            words.dedup();
-            words
+    /// Re-analyzes the document (debounced) and pushes any fresh
+    /// diagnostics back to core as ranged annotations, replacing whatever
+    /// this plugin previously reported for the view.
+    ///
+    /// NOTE: `View::update_annotations` is new plugin-lib surface this
+    /// commit depends on (an RPC analogous to the existing `view.edit`
+    /// and `view.completions` calls, taking an annotation type name and
+    /// the `(Interval, String)` spans to report); the corresponding
+    /// `xi_plugin_lib`/`xi_core_lib` change needs to land alongside this
+    /// one before it will compile against those crates.
+    fn run_diagnostics(&mut self, view: &mut View<ChunkCache>) {
+        let doc = match view.get_document() {
+            Ok(doc) => doc,
+            Err(e) => {
+                info!("error: {:?}", e);
+                return;
+            }
+        };
+        if let Some(diagnostics) = self.diagnostics.maybe_run(&doc) {
+            let spans = diagnostics
+                .iter()
+                .map(|d| (Interval::new(d.start, d.end), format!("{:?}: {}", d.severity, d.message)))
+                .collect();
+            view.update_annotations(ANNOTATION_TYPE, spans);
         }
     }
 
-    /// Attempts to find file path completion suggestions.
-    fn word_completions(
+    /// Renames every occurrence of the identifier under `pos` to
+    /// `new_name`, as a single atomic multi-region edit.
+    fn rename_at(&mut self, view: &mut View<ChunkCache>, pos: usize, new_name: &str) -> Result<(), Error> {
+        let line_nb = view.line_of_offset(pos)?;
+        let line_start = view.offset_of_line(line_nb)?;
+        let line = view.get_line(line_nb)?.to_owned();
+        let (_, target) = rename::word_under_cursor(&line, pos - line_start);
+
+        let doc = match view.get_document() {
+            Ok(doc) => doc,
+            Err(e) => {
+                info!("error: {:?}", e);
+                return Ok(());
+            }
+        };
+        if let Some(delta) = rename::rename_delta(&doc, view.get_buf_size(), &target, new_name) {
+            view.edit(delta, 0, false, true, "sample".into());
+        }
+        Ok(())
+    }
+
+    /// Builds the `CompletionContext` that every completion provider runs
+    /// against, by analyzing the buffer around `pos`.
+    fn build_completion_context(
         &self,
         view: &mut View<ChunkCache>,
         pos: usize,
-    ) -> Result<Vec<CompletionItem>, RemoteError> {
+    ) -> Result<CompletionContext, RemoteError> {
         let (word_start, word) = self.get_word_at_offset(view, pos);
-        let doc = match view.get_document() {
+        // `get_word_at_offset` reports the offset of the whitespace before
+        // the word; the word itself always ends at `pos`.
+        let word_start = word_start.max(pos - word.len());
+        let line_nb = view.line_of_offset(pos)?;
+        let line_start = view.offset_of_line(line_nb)?;
+        let line_prefix = view.get_line(line_nb)?[..pos - line_start].to_owned();
+        let prev_char = view.get_line(line_nb)?[..word_start - line_start].chars().last();
+        let doc_text = match view.get_document() {
             Ok(doc) => doc,
             Err(e) => {
                 info!("error: {:?}", e);
                 "".to_owned()
             }
         };
-        let completions = Self::complete_word(&word, &doc); // XXX
-        Ok(self.make_completions(view, completions, &word, word_start))
-    }
 
-    /// Given a word to complete and a list of viable paths to suggest,
-    /// constructs `CompletionItem`s.
-    fn make_completions(
-        &self,
-        view: &View<ChunkCache>,
-        words: Vec<String>,
-        word: &str,
-        word_off: usize,
-    ) -> Vec<CompletionItem> {
-        words
-            .iter()
-            .map(|w| {
-                let mut completion = CompletionItem::with_label(w);
-                let delta = RopeDelta::simple_edit(
-                    Interval::new(
-                        word_off, // XXX  start at begining of word or completion point?
-                        word_off + word.len(),
-                    ),
-                    w.into(),
-                    view.get_buf_size(),
-                );
-                completion.edit = Some(delta);
-                completion
-            })
-            .collect()
+        Ok(CompletionContext {
+            pos,
+            word_start,
+            word,
+            prev_char,
+            line_prefix,
+            doc_path: view.get_path().map(Path::to_owned),
+            doc_text,
+            buf_size: view.get_buf_size(),
+        })
     }
 
     fn get_word_at_offset(&self, view: &mut View<ChunkCache>, offset: usize) -> (usize, String) {
@@ -308,6 +371,6 @@ fn main() {
         logging_path_result.as_ref().map(|p: &PathBuf| -> &Path { p.as_path() }).ok();
     setup_logging(logging_path);
 
-    let mut plugin = SamplePlugin;
+    let mut plugin = SamplePlugin::new();
     mainloop(&mut plugin).unwrap();
 }
@@ -0,0 +1,109 @@
+// Copyright 2019 The xi-editor Authors.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Whole-document rename support for the sample plugin: finds every
+//! exact, word-boundary-delimited occurrence of a target identifier and
+//! rewrites them all as a single multi-region edit.
+
+use xi_rope::delta::Builder as EditBuilder;
+use xi_rope::interval::Interval;
+use xi_rope::rope::RopeDelta;
+
+/// Whether `c` can be part of an identifier.
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Finds the identifier spanning `offset_in_line`: the maximal run of
+/// identifier characters on *both* sides of the offset, not just the
+/// prefix before it. E.g. with `line = "let fooBar = 1;"` and an offset
+/// anywhere inside `fooBar`, this returns `(4, "fooBar")`, not just the
+/// portion up to the cursor.
+pub fn word_under_cursor(line: &str, offset_in_line: usize) -> (usize, String) {
+    let before: usize =
+        line[..offset_in_line].chars().rev().take_while(|&c| is_ident_char(c)).map(char::len_utf8).sum();
+    let after: usize =
+        line[offset_in_line..].chars().take_while(|&c| is_ident_char(c)).map(char::len_utf8).sum();
+    let start = offset_in_line - before;
+    let end = offset_in_line + after;
+    (start, line[start..end].to_owned())
+}
+
+/// Returns the byte offset of every occurrence of `target` in `doc` that
+/// is bounded by non-identifier characters (or the start/end of the
+/// document) on both sides, so that e.g. `foo` does not match inside
+/// `foobar` or `barfoo`.
+fn find_occurrences(doc: &str, target: &str) -> Vec<usize> {
+    if target.is_empty() {
+        return Vec::new();
+    }
+    let is_boundary = |c: Option<char>| !c.map(is_ident_char).unwrap_or(false);
+    doc.match_indices(target)
+        .filter(|&(start, matched)| {
+            let before = doc[..start].chars().last();
+            let after = doc[start + matched.len()..].chars().next();
+            is_boundary(before) && is_boundary(after)
+        })
+        .map(|(start, _)| start)
+        .collect()
+}
+
+/// Builds the `RopeDelta` that replaces every occurrence of `target` in
+/// `doc` with `new_name`, in a single edit, or `None` if `target` does
+/// not occur.
+pub fn rename_delta(doc: &str, buf_size: usize, target: &str, new_name: &str) -> Option<RopeDelta> {
+    let offsets = find_occurrences(doc, target);
+    if offsets.is_empty() {
+        return None;
+    }
+
+    let mut builder = EditBuilder::new(buf_size);
+    for start in offsets {
+        let iv = Interval::new(start, start + target.len());
+        builder.replace(iv, new_name.into());
+    }
+    Some(builder.build())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{find_occurrences, word_under_cursor};
+
+    #[test]
+    fn word_under_cursor_spans_both_sides() {
+        let line = "let fooBar = 1; let foo = 2;";
+        // cursor right after "foo" within "fooBar"
+        let (start, word) = word_under_cursor(line, 7);
+        assert_eq!((start, word.as_str()), (4, "fooBar"));
+    }
+
+    #[test]
+    fn word_under_cursor_at_start_of_word_still_finds_it() {
+        let line = "let fooBar = 1;";
+        let (start, word) = word_under_cursor(line, 4);
+        assert_eq!((start, word.as_str()), (4, "fooBar"));
+    }
+
+    #[test]
+    fn find_occurrences_respects_identifier_boundaries() {
+        let doc = "let foo = 1; let foobar = 2; let barfoo = 3;";
+        let offsets = find_occurrences(doc, "foo");
+        assert_eq!(offsets, vec![4]);
+    }
+
+    #[test]
+    fn find_occurrences_of_empty_target_is_empty() {
+        assert!(find_occurrences("let foo = 1;", "").is_empty());
+    }
+}